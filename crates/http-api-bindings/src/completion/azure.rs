@@ -6,13 +6,16 @@ use futures::stream::BoxStream;
 use tabby_inference::{CompletionOptions, CompletionStream};
 use tracing::warn;
 
+use super::{build_http_client, establish_stream, ExtraConfig, RetryConfig};
+
 pub struct AzureEngine {
     client: async_openai::Client<AzureConfig>,
     model_name: String,
+    retry: RetryConfig,
 }
 
 impl AzureEngine {
-    pub fn create(api_endpoint: &str, api_version: &str, deployment_id: &str, model_name: &str, api_key: Option<String>) -> Self {
+    pub fn create(api_endpoint: &str, api_version: &str, deployment_id: &str, model_name: &str, api_key: Option<String>, extra: ExtraConfig) -> Self {
 
         let config = AzureConfig::default()
             .with_api_base(api_endpoint)
@@ -20,13 +23,22 @@ impl AzureEngine {
             .with_deployment_id(deployment_id)
             .with_api_key(api_key.unwrap_or_default());
 
-        let client = async_openai::Client::with_config(config);
+        let client = async_openai::Client::with_config(config)
+            .with_http_client(build_http_client(&extra));
 
         Self {
             client,
             model_name: model_name.to_owned(),
+            retry: RetryConfig::default(),
         }
     }
+
+    /// Sets the connection-establishment retry policy. Defaults to
+    /// [`RetryConfig::default`].
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
 }
 
 #[async_trait]
@@ -42,11 +54,9 @@ impl CompletionStream for AzureEngine {
             ])
             .temperature(options.sampling_temperature)
             .max_tokens(options.max_decoding_tokens as u16)
-            // .stream(true)
+            .stream(true)
             .build();
 
-        println!("{:?}", request);
-
         let s = stream! {
             let request = match request {
                 Ok(x) => x,
@@ -56,40 +66,29 @@ impl CompletionStream for AzureEngine {
                 }
             };
 
-            let s = match self.client.chat().create(request).await {
-                Ok(x) => x,
-                Err(e) => {
-                    warn!("Failed to create completion request {:?}", e);
-                    return;
-                }
+            let (s, first) = match establish_stream(self.retry, || self.client.chat().create_stream(request.clone())).await {
+                Some(x) => x,
+                None => return,
             };
 
-            // for await x in s {
-            //     match x {
-            //         Ok(x) => {
-            //             println!("----azure response---- {:?}", x);
-            //             if x.choices.len() == 0 {
-            //                 break;
-            //             }
-            //             yield x.choices[0].delta.content.clone().unwrap();
-            //         },
-            //         Err(OpenAIError::StreamError(_)) => {
-            //             warn!("Stream error");
-            //             break;
-            //         },
-            //         Err(e) => {
-            //             warn!("Failed to stream response: {}", e);
-            //             break;
-            //         }
-            //     };
-            // }
-
-            println!("----azure response---- {:?}", s);
-            if s.choices.len() == 0 {
-                warn!("Empty choice from Azure {:#?}", s);
-                return;
+            if let Some(content) = first.choices.first().and_then(|c| c.delta.content.clone()) {
+                yield content;
+            }
+
+            for await x in s {
+                match x {
+                    Ok(x) => {
+                        if let Some(content) = x.choices.first().and_then(|c| c.delta.content.clone()) {
+                            yield content;
+                        }
+                    },
+                    Err(OpenAIError::StreamError(_)) => break,
+                    Err(e) => {
+                        warn!("Failed to stream response: {}", e);
+                        break;
+                    }
+                };
             }
-            yield s.choices[0].message.content.clone().unwrap();
         };
 
         Box::pin(s)
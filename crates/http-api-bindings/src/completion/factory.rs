@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use tabby_inference::CompletionStream;
+
+use super::{AzureEngine, EngineMode, ExtraConfig, OpenAIEngine, RetryConfig};
+
+/// Declarative description of a completion backend.
+///
+/// Selecting a backend by type name keeps engine construction in one place: a
+/// new OpenAI-compatible provider is a single additional arm in
+/// [`build_engine`] rather than bespoke wiring at each call site. Every arm
+/// carries the transport ([`ExtraConfig`]) and retry ([`RetryConfig`]) knobs so
+/// centralizing construction doesn't drop the features the engines expose; the
+/// OpenAI-style arms additionally carry the endpoint [`EngineMode`].
+#[derive(Debug, Clone)]
+pub enum BackendConfig {
+    OpenAI {
+        api_endpoint: String,
+        model_name: String,
+        api_key: Option<String>,
+        organization_id: Option<String>,
+        mode: EngineMode,
+        retry: RetryConfig,
+        extra: ExtraConfig,
+    },
+    Azure {
+        api_endpoint: String,
+        api_version: String,
+        deployment_id: String,
+        model_name: String,
+        api_key: Option<String>,
+        retry: RetryConfig,
+        extra: ExtraConfig,
+    },
+    /// Any server exposing an OpenAI-compatible API (self-hosted vLLM/TGI, …).
+    OpenAICompatible {
+        api_base: String,
+        model_name: String,
+        api_key: Option<String>,
+        organization_id: Option<String>,
+        mode: EngineMode,
+        retry: RetryConfig,
+        extra: ExtraConfig,
+    },
+}
+
+/// Builds the [`CompletionStream`] engine described by `cfg`.
+pub fn build_engine(cfg: &BackendConfig) -> Arc<dyn CompletionStream> {
+    match cfg {
+        BackendConfig::OpenAI {
+            api_endpoint,
+            model_name,
+            api_key,
+            organization_id,
+            mode,
+            retry,
+            extra,
+        } => Arc::new(
+            OpenAIEngine::create(
+                api_endpoint,
+                model_name,
+                api_key.clone(),
+                organization_id.clone(),
+                extra.clone(),
+            )
+            .with_mode(mode.clone())
+            .with_retry_config(*retry),
+        ),
+        BackendConfig::Azure {
+            api_endpoint,
+            api_version,
+            deployment_id,
+            model_name,
+            api_key,
+            retry,
+            extra,
+        } => Arc::new(
+            AzureEngine::create(
+                api_endpoint,
+                api_version,
+                deployment_id,
+                model_name,
+                api_key.clone(),
+                extra.clone(),
+            )
+            .with_retry_config(*retry),
+        ),
+        BackendConfig::OpenAICompatible {
+            api_base,
+            model_name,
+            api_key,
+            organization_id,
+            mode,
+            retry,
+            extra,
+        } => Arc::new(
+            OpenAIEngine::create(
+                api_base,
+                model_name,
+                api_key.clone(),
+                organization_id.clone(),
+                extra.clone(),
+            )
+            .with_mode(mode.clone())
+            .with_retry_config(*retry),
+        ),
+    }
+}
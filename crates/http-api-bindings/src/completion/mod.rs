@@ -0,0 +1,287 @@
+mod azure;
+mod factory;
+mod openai;
+mod routing;
+
+pub use azure::AzureEngine;
+pub use factory::{build_engine, BackendConfig};
+pub use openai::{EngineMode, OpenAIEngine, PromptTemplate};
+pub use routing::RoutingEngine;
+
+use async_openai::error::OpenAIError;
+use futures::{Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Proxy;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Extra transport-level configuration shared by the OpenAI-compatible engines.
+///
+/// These knobs control the underlying `reqwest` client rather than the
+/// completion request itself, so they can be applied uniformly to every engine
+/// that talks to an OpenAI-style endpoint.
+#[derive(Debug, Default, Clone)]
+pub struct ExtraConfig {
+    /// Proxy URL (`http://`, `https://` or `socks5://`). When unset, the proxy
+    /// is taken from the `HTTPS_PROXY` / `ALL_PROXY` environment variables.
+    pub proxy: Option<String>,
+    /// Connection-establishment timeout in seconds.
+    pub connect_timeout_secs: Option<u64>,
+    /// Additional headers attached to every request (e.g. gateway auth).
+    pub headers: Vec<(String, String)>,
+}
+
+/// Builds a `reqwest::Client` from [`ExtraConfig`], falling back to a default
+/// client when no extra options are set or when the options fail to apply.
+pub(crate) fn build_http_client(extra: &ExtraConfig) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+
+    let proxy = extra
+        .proxy
+        .clone()
+        .or_else(|| std::env::var("HTTPS_PROXY").ok())
+        .or_else(|| std::env::var("ALL_PROXY").ok());
+    if let Some(proxy) = proxy {
+        match Proxy::all(&proxy) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => warn!("Ignoring invalid proxy {:?}: {}", proxy, e),
+        }
+    }
+
+    if let Some(secs) = extra.connect_timeout_secs {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+
+    if !extra.headers.is_empty() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &extra.headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                _ => warn!("Ignoring invalid header {:?}", name),
+            }
+        }
+        builder = builder.default_headers(headers);
+    }
+
+    builder.build().unwrap_or_default()
+}
+
+/// Exponential-backoff policy applied to the connection-establishment phase of a
+/// completion request. Only the first frame is retried; once a token has been
+/// emitted, mid-stream errors are never replayed so already-emitted tokens are
+/// not duplicated.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 6,
+            base_delay_ms: 500,
+        }
+    }
+}
+
+/// Returns true for errors worth retrying: rate limits (HTTP 429), server-side
+/// failures (HTTP 5xx) and transport-level connection/timeout errors.
+fn is_retryable(error: &OpenAIError) -> bool {
+    match error {
+        OpenAIError::Reqwest(e) => {
+            e.is_connect()
+                || e.is_timeout()
+                || matches!(e.status(), Some(s) if s.as_u16() == 429 || s.is_server_error())
+        }
+        OpenAIError::ApiError(e) => {
+            let rate_limited = e.code.as_deref() == Some("rate_limit_exceeded")
+                || e.r#type.as_deref() == Some("rate_limit_exceeded")
+                || e.message.to_lowercase().contains("rate limit");
+            rate_limited
+        }
+        _ => false,
+    }
+}
+
+fn backoff_delay(base_delay_ms: u64, attempt: u32) -> Duration {
+    let exp = base_delay_ms.saturating_mul(1u64 << attempt.min(16));
+    // Add jitter in `[0, base_delay_ms)` to avoid synchronized retries.
+    let jitter = if base_delay_ms == 0 {
+        0
+    } else {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0) as u64;
+        nanos % base_delay_ms
+    };
+    Duration::from_millis(exp.saturating_add(jitter))
+}
+
+/// Establishes a completion stream with exponential-backoff retries.
+///
+/// With async-openai's SSE streaming, `create_stream` resolves to `Ok` before
+/// the HTTP status is read, so a 429/5xx (or a lazily-sent connection error)
+/// surfaces as the *first stream frame* rather than as an `Err` from the call.
+/// To actually retry the errors the ticket names, this both retries the
+/// `connect` call and peeks the first frame: if either reports a transient
+/// error (per [`is_retryable`]), the connection is re-established under backoff.
+///
+/// On success it returns the live stream together with the already-consumed
+/// first frame, which the caller must emit before draining the rest. Only this
+/// first frame is retried — once it has been yielded, mid-stream errors
+/// terminate the stream cleanly so no token is duplicated. Returns `None` when
+/// the stream is empty, ends with a non-retryable first frame, or exhausts its
+/// retries.
+pub(crate) async fn establish_stream<F, Fut, S, T>(
+    retry: RetryConfig,
+    mut connect: F,
+) -> Option<(S, T)>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<S, OpenAIError>>,
+    S: Stream<Item = Result<T, OpenAIError>> + Unpin,
+{
+    let mut attempt = 0;
+    loop {
+        let attempt_result = async {
+            let mut stream = connect().await?;
+            match stream.next().await {
+                Some(Ok(first)) => Ok(Some((stream, first))),
+                Some(Err(e)) => Err(e),
+                None => Ok(None),
+            }
+        }
+        .await;
+
+        let error = match attempt_result {
+            Ok(result) => return result,
+            Err(e) => e,
+        };
+
+        if attempt >= retry.max_retries || !is_retryable(&error) {
+            warn!("Failed to establish completion stream: {}", error);
+            return None;
+        }
+
+        let delay = backoff_delay(retry.base_delay_ms, attempt);
+        warn!(
+            "Retrying completion request after error (attempt {}/{}): {}",
+            attempt + 1,
+            retry.max_retries,
+            error
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::error::ApiError;
+    use futures::stream;
+    use std::cell::Cell;
+    use std::pin::Pin;
+
+    type TestStream = Pin<Box<dyn Stream<Item = Result<String, OpenAIError>> + Send>>;
+
+    fn rate_limit_error() -> OpenAIError {
+        OpenAIError::ApiError(ApiError {
+            message: "rate limit reached".to_owned(),
+            r#type: Some("rate_limit_exceeded".to_owned()),
+            param: None,
+            code: Some("rate_limit_exceeded".to_owned()),
+        })
+    }
+
+    fn frames(frames: Vec<Result<String, OpenAIError>>) -> TestStream {
+        Box::pin(stream::iter(frames))
+    }
+
+    #[test]
+    fn test_is_retryable_rate_limit() {
+        assert!(is_retryable(&rate_limit_error()));
+        assert!(!is_retryable(&OpenAIError::StreamError("boom".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn test_establish_stream_retries_rate_limit_first_frame() {
+        let retry = RetryConfig {
+            max_retries: 3,
+            base_delay_ms: 0,
+        };
+        let attempts = Cell::new(0u32);
+
+        // The first two connections' streams open fine but their first frame is a
+        // 429; establish_stream must re-connect and succeed on the third attempt.
+        let established = establish_stream(retry, || {
+            let attempt = attempts.get() + 1;
+            attempts.set(attempt);
+            async move {
+                if attempt < 3 {
+                    Ok(frames(vec![Err(rate_limit_error())]))
+                } else {
+                    Ok(frames(vec![Ok("hello".to_owned()), Ok(" world".to_owned())]))
+                }
+            }
+        })
+        .await;
+
+        let (mut rest, first) = established.expect("stream should establish after retries");
+        assert_eq!(first, "hello");
+        assert!(matches!(rest.next().await, Some(Ok(ref s)) if s == " world"));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_establish_stream_retries_connect_error() {
+        let retry = RetryConfig {
+            max_retries: 3,
+            base_delay_ms: 0,
+        };
+        let attempts = Cell::new(0u32);
+
+        let established = establish_stream(retry, || {
+            let attempt = attempts.get() + 1;
+            attempts.set(attempt);
+            async move {
+                if attempt < 2 {
+                    Err(rate_limit_error())
+                } else {
+                    Ok(frames(vec![Ok("ok".to_owned())]))
+                }
+            }
+        })
+        .await;
+
+        let (_rest, first) = established.expect("stream should establish after retries");
+        assert_eq!(first, "ok");
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_establish_stream_gives_up_on_non_retryable() {
+        let retry = RetryConfig {
+            max_retries: 5,
+            base_delay_ms: 0,
+        };
+        let attempts = Cell::new(0u32);
+
+        let established = establish_stream(retry, || {
+            attempts.set(attempts.get() + 1);
+            async { Ok(frames(vec![Err(OpenAIError::StreamError("boom".to_owned()))])) }
+        })
+        .await;
+
+        assert!(established.is_none());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -1,3 +1,7 @@
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    CreateChatCompletionRequestArgs,
+};
 use async_openai::{config::OpenAIConfig, error::OpenAIError, types::CreateCompletionRequestArgs};
 use async_stream::stream;
 use async_trait::async_trait;
@@ -5,29 +9,96 @@ use futures::stream::BoxStream;
 use tabby_inference::{CompletionOptions, CompletionStream};
 use tracing::warn;
 
+use super::{build_http_client, establish_stream, ExtraConfig, RetryConfig};
+
+/// Formats an incoming prompt into the text sent to the model.
+///
+/// The template is a plain string with a single `{prompt}` placeholder, which
+/// is substituted with the (FIM-expanded) prompt produced upstream. The default
+/// template forwards the prompt verbatim.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    template: String,
+}
+
+impl PromptTemplate {
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: template.to_owned(),
+        }
+    }
+
+    fn apply(&self, prompt: &str) -> String {
+        self.template.replace("{prompt}", prompt)
+    }
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self::new("{prompt}")
+    }
+}
+
+/// Selects which OpenAI endpoint family the engine targets.
+#[derive(Debug, Clone)]
+pub enum EngineMode {
+    /// Legacy `/v1/completions` endpoint.
+    Completions,
+    /// `/v1/chat/completions` endpoint, wrapping the prompt into a chat message.
+    Chat {
+        system_prompt: Option<String>,
+        template: PromptTemplate,
+    },
+}
+
+impl Default for EngineMode {
+    fn default() -> Self {
+        Self::Completions
+    }
+}
+
 pub struct OpenAIEngine {
     client: async_openai::Client<OpenAIConfig>,
     model_name: String,
+    retry: RetryConfig,
+    mode: EngineMode,
 }
 
 impl OpenAIEngine {
-    pub fn create(api_endpoint: &str, model_name: &str, api_key: Option<String>) -> Self {
-        let config = OpenAIConfig::default()
+    pub fn create(api_endpoint: &str, model_name: &str, api_key: Option<String>, organization_id: Option<String>, extra: ExtraConfig) -> Self {
+        let mut config = OpenAIConfig::default()
             .with_api_base(api_endpoint)
             .with_api_key(api_key.unwrap_or_default());
+        if let Some(organization_id) = organization_id {
+            config = config.with_org_id(organization_id);
+        }
 
-        let client = async_openai::Client::with_config(config);
+        let client = async_openai::Client::with_config(config)
+            .with_http_client(build_http_client(&extra));
 
         Self {
             client,
             model_name: model_name.to_owned(),
+            retry: RetryConfig::default(),
+            mode: EngineMode::default(),
         }
     }
-}
 
-#[async_trait]
-impl CompletionStream for OpenAIEngine {
-    async fn generate(&self, prompt: &str, options: CompletionOptions) -> BoxStream<String> {
+    /// Selects the endpoint family used by this engine. Defaults to
+    /// [`EngineMode::Completions`].
+    pub fn with_mode(mut self, mode: EngineMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the connection-establishment retry policy. Defaults to
+    /// [`RetryConfig::default`].
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn generate_completions(&self, prompt: &str, options: CompletionOptions) -> BoxStream<String> {
         let request = CreateCompletionRequestArgs::default()
             .model(&self.model_name)
             .temperature(options.sampling_temperature)
@@ -45,18 +116,92 @@ impl CompletionStream for OpenAIEngine {
                 }
             };
 
-            let s = match self.client.completions().create_stream(request).await {
-                Ok(x) => x,
-                Err(e) => {
-                    warn!("Failed to create completion request {:?}", e);
+            let (s, first) = match establish_stream(self.retry, || self.client.completions().create_stream(request.clone())).await {
+                Some(x) => x,
+                None => return,
+            };
+
+            if let Some(text) = first.choices.first().map(|c| c.text.clone()) {
+                yield text;
+            }
+
+            for await x in s {
+                match x {
+                    Ok(x) => {
+                        if let Some(text) = x.choices.first().map(|c| c.text.clone()) {
+                            yield text;
+                        }
+                    },
+                    Err(OpenAIError::StreamError(_)) => break,
+                    Err(e) => {
+                        warn!("Failed to stream response: {}", e);
+                        break;
+                    }
+                };
+            }
+        };
+
+        Box::pin(s)
+    }
+
+    fn generate_chat(
+        &self,
+        prompt: &str,
+        options: CompletionOptions,
+        system_prompt: Option<String>,
+        template: PromptTemplate,
+    ) -> BoxStream<String> {
+        let user_content = template.apply(prompt);
+
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = system_prompt {
+            match ChatCompletionRequestSystemMessageArgs::default()
+                .content(system_prompt)
+                .build()
+            {
+                Ok(message) => messages.push(message.into()),
+                Err(e) => warn!("Failed to build system message {:?}", e),
+            }
+        }
+
+        let request = ChatCompletionRequestUserMessageArgs::default()
+            .content(user_content)
+            .build()
+            .map(|message| {
+                messages.push(message.into());
+                CreateChatCompletionRequestArgs::default()
+                    .model(&self.model_name)
+                    .messages(messages)
+                    .temperature(options.sampling_temperature)
+                    .max_tokens(options.max_decoding_tokens as u16)
+                    .stream(true)
+                    .build()
+            });
+
+        let s = stream! {
+            let request = match request {
+                Ok(Ok(x)) => x,
+                Ok(Err(e)) | Err(e) => {
+                    warn!("Failed to build completion request {:?}", e);
                     return;
                 }
             };
 
+            let (s, first) = match establish_stream(self.retry, || self.client.chat().create_stream(request.clone())).await {
+                Some(x) => x,
+                None => return,
+            };
+
+            if let Some(content) = first.choices.first().and_then(|c| c.delta.content.clone()) {
+                yield content;
+            }
+
             for await x in s {
                 match x {
                     Ok(x) => {
-                        yield x.choices[0].text.clone();
+                        if let Some(content) = x.choices.first().and_then(|c| c.delta.content.clone()) {
+                            yield content;
+                        }
                     },
                     Err(OpenAIError::StreamError(_)) => break,
                     Err(e) => {
@@ -71,6 +216,19 @@ impl CompletionStream for OpenAIEngine {
     }
 }
 
+#[async_trait]
+impl CompletionStream for OpenAIEngine {
+    async fn generate(&self, prompt: &str, options: CompletionOptions) -> BoxStream<String> {
+        match self.mode.clone() {
+            EngineMode::Completions => self.generate_completions(prompt, options),
+            EngineMode::Chat {
+                system_prompt,
+                template,
+            } => self.generate_chat(prompt, options, system_prompt, template),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
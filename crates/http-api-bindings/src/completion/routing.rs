@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream};
+use tabby_inference::{CompletionOptions, CompletionStream};
+use tracing::warn;
+
+/// A single route binding a model-name pattern to a backend engine.
+struct Route {
+    pattern: String,
+    backend: Arc<dyn CompletionStream>,
+}
+
+/// Dispatches completion requests to one of several named backends based on the
+/// configured model name.
+///
+/// Each route matches the model name either exactly or, when the pattern ends
+/// with `*`, by prefix (e.g. `gpt-*` routes every `gpt-…` model to an
+/// [`OpenAIEngine`](super::OpenAIEngine)). This lets a single Tabby instance
+/// front several OpenAI-compatible providers, each with its own base URL and
+/// key, rather than being locked to one endpoint.
+pub struct RoutingEngine {
+    model_name: String,
+    routes: Vec<Route>,
+    default: Option<Arc<dyn CompletionStream>>,
+}
+
+impl RoutingEngine {
+    pub fn new(model_name: &str) -> Self {
+        Self {
+            model_name: model_name.to_owned(),
+            routes: Vec::new(),
+            default: None,
+        }
+    }
+
+    /// Registers a backend for models matching `pattern`. A trailing `*` makes
+    /// the match a prefix match; otherwise the model name must match exactly.
+    pub fn route(mut self, pattern: &str, backend: Arc<dyn CompletionStream>) -> Self {
+        self.routes.push(Route {
+            pattern: pattern.to_owned(),
+            backend,
+        });
+        self
+    }
+
+    /// Sets the fallback backend used when no route matches the model name.
+    pub fn default_backend(mut self, backend: Arc<dyn CompletionStream>) -> Self {
+        self.default = Some(backend);
+        self
+    }
+
+    fn select(&self) -> Option<&Arc<dyn CompletionStream>> {
+        self.routes
+            .iter()
+            .find(|route| matches_pattern(&route.pattern, &self.model_name))
+            .map(|route| &route.backend)
+            .or(self.default.as_ref())
+    }
+}
+
+fn matches_pattern(pattern: &str, model_name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model_name.starts_with(prefix),
+        None => pattern == model_name,
+    }
+}
+
+#[async_trait]
+impl CompletionStream for RoutingEngine {
+    async fn generate(&self, prompt: &str, options: CompletionOptions) -> BoxStream<String> {
+        match self.select() {
+            Some(backend) => backend.generate(prompt, options).await,
+            None => {
+                warn!("No backend matches model {:?}", self.model_name);
+                Box::pin(stream::empty())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("gpt-*", "gpt-3.5-turbo"));
+        assert!(matches_pattern("mistral*", "mistral-7b"));
+        assert!(matches_pattern("danone-gpt4-32k", "danone-gpt4-32k"));
+        assert!(!matches_pattern("gpt-*", "mistral-7b"));
+        assert!(!matches_pattern("danone-gpt4-32k", "gpt-4"));
+    }
+}